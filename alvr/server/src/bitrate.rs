@@ -0,0 +1,166 @@
+use std::time::{Duration, Instant};
+
+use crate::statistics::{GoodputEstimate, NominalBitrateStats};
+
+// Bitrate/framerate the encoder should use for the next frame.
+#[derive(Clone, Copy)]
+pub struct DynamicEncoderParams {
+    pub bitrate_bps: u64,
+    pub framerate: f32,
+}
+
+const STARTUP_BITRATE_BPS: u64 = 30_000_000;
+// Don't react to every single frame's jitter: only revisit the bitrate once this much time has
+// passed since it was last revisited (whether or not that changed it), so a momentary blip
+// doesn't cause an oscillation.
+const MIN_UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+// Congestion (goodput falling below the current bitrate) is corrected immediately and in full;
+// recovering bitrate after things improve is deliberately much slower, so the link isn't re-tested
+// into congestion every time the encoder overshoots a half second of idle headroom.
+const MAX_STEP_UP_FRACTION: f64 = 0.10;
+const MAX_STEP_DOWN_FRACTION: f64 = 1.0;
+// A decision has to move the bitrate by at least this much to be worth taking at all; otherwise
+// we'd be chasing measurement noise every update interval.
+const HYSTERESIS_FRACTION: f64 = 0.05;
+// Encoder latency above this fraction of the frame interval means the encoder itself can't keep
+// up (independent of the network), so back off even if goodput looks fine.
+const ENCODER_LATENCY_OVERRUN_FRACTION: f64 = 0.8;
+// A growing network send queue shows up as rising end-to-end latency before it shows up as
+// reduced goodput. Occupancy past this much extra delay stops any step-up; on its own it does not
+// lower the bitrate, which only drops once goodput falls below it.
+const BUFFER_OCCUPANCY_CONGESTION_THRESHOLD: Duration = Duration::from_millis(30);
+
+pub struct BitrateManager {
+    frame_interval: Duration,
+    nominal_bitrate_bps: u64,
+    last_update_instant: Instant,
+    last_encoder_latency: Duration,
+    // Seeds `nominal_bitrate_bps` from the user's configured starting point on the first call to
+    // `get_encoder_params` (the config isn't available yet in `new()`, since `BITRATE_MANAGER` is
+    // constructed before any connection/settings are known).
+    seeded_from_config: bool,
+}
+
+impl BitrateManager {
+    pub fn new(_max_history_size: usize, initial_framerate: f32) -> Self {
+        Self {
+            frame_interval: Duration::from_secs_f32(1.0 / initial_framerate.max(1.0)),
+            nominal_bitrate_bps: STARTUP_BITRATE_BPS,
+            last_update_instant: Instant::now(),
+            last_encoder_latency: Duration::ZERO,
+            seeded_from_config: false,
+        }
+    }
+
+    pub fn last_nominal_bitrate_bps(&self) -> u64 {
+        self.nominal_bitrate_bps
+    }
+
+    pub fn report_frame_present(&mut self, adapt_to_framerate: bool) {
+        if !adapt_to_framerate {
+            return;
+        }
+    }
+
+    pub fn report_frame_encoded(
+        &mut self,
+        _target_timestamp: Duration,
+        encoder_latency: Duration,
+        _encoded_bytes: usize,
+    ) {
+        self.last_encoder_latency = encoder_latency;
+    }
+
+    // Applies hysteresis around the current bitrate so a link whose goodput falls below the
+    // current bitrate steps down immediately (never leave the user staring at a frozen/corrupted
+    // stream), while recovery climbs back up
+    // gradually once goodput and encoder latency both look healthy again. The result is always
+    // kept within `config`'s configured bounds, so a user-set cap or floor is never overridden by
+    // the dynamic estimate. Returns `None` when `MIN_UPDATE_INTERVAL` hasn't elapsed yet, so the
+    // caller can skip reconfiguring the encoder on frames where nothing changed.
+    pub fn get_encoder_params(
+        &mut self,
+        config: &alvr_session::BitrateConfig,
+        network_estimate: Option<GoodputEstimate>,
+    ) -> Option<(DynamicEncoderParams, NominalBitrateStats)> {
+        if self.last_update_instant.elapsed() < MIN_UPDATE_INTERVAL {
+            return None;
+        }
+        self.last_update_instant = Instant::now();
+
+        let min_bitrate_bps = config.min_bitrate_mbps.map(|mbps| mbps * 1_000_000);
+        let max_bitrate_bps = config.max_bitrate_mbps.map(|mbps| mbps * 1_000_000);
+
+        if !self.seeded_from_config {
+            self.seeded_from_config = true;
+            if let Some(start_mbps) = config.start_bitrate_mbps {
+                self.nominal_bitrate_bps = start_mbps * 1_000_000;
+            }
+        }
+        self.nominal_bitrate_bps = clamp_to_config_bounds(self.nominal_bitrate_bps, min_bitrate_bps, max_bitrate_bps);
+
+        let current = self.nominal_bitrate_bps as f64;
+        let mut target = current;
+        let mut stats = NominalBitrateStats {
+            scaled_calculated_bitrate_bps: self.nominal_bitrate_bps,
+            ..Default::default()
+        };
+
+        if let Some(estimate) = network_estimate.filter(|estimate| estimate.bps > 0) {
+            stats.network_goodput_limiter_bitrate_bps = Some(estimate.bps);
+            stats.network_queue_occupancy_ms = Some(estimate.buffer_occupancy.as_millis() as u64);
+
+            let queueing = estimate.buffer_occupancy > BUFFER_OCCUPANCY_CONGESTION_THRESHOLD;
+
+            if (estimate.bps as f64) < current || queueing {
+                // Congestion: either the network can no longer sustain the current bitrate, or its
+                // send queue is growing (a leading indicator that shows up before goodput visibly
+                // drops). Step down to the observed goodput right away. When only the queue is
+                // growing and goodput still covers the current bitrate, the bitrate is held where
+                // it is, not lowered.
+                target = (estimate.bps as f64).min(current);
+            } else {
+                // Headroom, and no sign of a growing queue: climb back up cautiously.
+                target = current * (1.0 + MAX_STEP_UP_FRACTION);
+            }
+        }
+
+        let frame_interval_s = self.frame_interval.as_secs_f64().max(1e-6);
+        if self.last_encoder_latency.as_secs_f64() > frame_interval_s * ENCODER_LATENCY_OVERRUN_FRACTION {
+            stats.encoder_latency_limiter_bitrate_bps = Some((current * (1.0 - MAX_STEP_DOWN_FRACTION * 0.5)) as u64);
+            target = target.min(current * (1.0 - MAX_STEP_DOWN_FRACTION * 0.5));
+        }
+
+        // Bound how far a single decision can move the bitrate, then throw away moves too small to
+        // matter (hysteresis), so near-equilibrium traffic doesn't cause a new encoder
+        // reconfiguration every update interval.
+        let max_up = current * (1.0 + MAX_STEP_UP_FRACTION);
+        let max_down = current * (1.0 - MAX_STEP_DOWN_FRACTION);
+        target = target.clamp(max_down, max_up).max(1.0);
+
+        // The user's configured bounds always win, even over a congestion step-down: a configured
+        // floor means "never go below this, accept the jank" just as much as a configured ceiling
+        // means "never go above this, even if the link has room."
+        target = clamp_to_config_bounds(target as u64, min_bitrate_bps, max_bitrate_bps) as f64;
+
+        if (target - current).abs() < current * HYSTERESIS_FRACTION {
+            return None;
+        }
+
+        self.nominal_bitrate_bps = target as u64;
+        stats.scaled_calculated_bitrate_bps = self.nominal_bitrate_bps;
+
+        Some((
+            DynamicEncoderParams {
+                bitrate_bps: self.nominal_bitrate_bps,
+                framerate: 1.0 / frame_interval_s as f32,
+            },
+            stats,
+        ))
+    }
+}
+
+fn clamp_to_config_bounds(bitrate_bps: u64, min_bitrate_bps: Option<u64>, max_bitrate_bps: Option<u64>) -> u64 {
+    let bitrate_bps = min_bitrate_bps.map_or(bitrate_bps, |min| bitrate_bps.max(min));
+    max_bitrate_bps.map_or(bitrate_bps, |max| bitrate_bps.min(max))
+}
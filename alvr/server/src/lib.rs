@@ -43,6 +43,8 @@ use alvr_packets::{
 use alvr_server_io::ServerDataManager;
 use alvr_session::{CodecType, OpenvrProperty, Settings};
 use bitrate::{BitrateManager, DynamicEncoderParams};
+use rand::Rng;
+use serde::Serialize;
 use statistics::StatisticsManager;
 use std::{
     collections::VecDeque,
@@ -50,8 +52,9 @@ use std::{
     ffi::CString,
     fs::File,
     io::Write,
+    path::PathBuf,
     sync::{
-        atomic::{AtomicBool, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
         mpsc::TrySendError,
     },
     thread::{self, JoinHandle},
@@ -88,6 +91,21 @@ pub enum ServerCoreEvent {
     GameRenderLatencyFeedback(Duration), // only used for SteamVR
     ShutdownPending,
     RestartPending,
+    CodecDowngraded {
+        configured: CodecType,
+        negotiated: CodecType,
+    },
+    NoSupportedCodec {
+        configured: CodecType,
+    },
+    StreamStalled {
+        stall_duration: Duration,
+    },
+    DiagnosticClipCaptured {
+        path: PathBuf,
+        captured_at: Duration,
+        nominal_bitrate_bps: u64,
+    },
 }
 
 pub static EVENTS_QUEUE: Mutex<VecDeque<ServerCoreEvent>> = Mutex::new(VecDeque::new());
@@ -96,6 +114,16 @@ pub static LIFECYCLE_STATE: RwLock<LifecycleState> = RwLock::new(LifecycleState:
 pub static IS_RESTARTING: RelaxedAtomic = RelaxedAtomic::new(false);
 static CONNECTION_THREAD: RwLock<Option<JoinHandle<()>>> = RwLock::new(None);
 
+// Last time `send_video_nal` made progress. Watched by the stall-detection thread so an encoder
+// or compositor hang gets force-recovered instead of silently holding the stream open forever.
+static LAST_VIDEO_NAL_INSTANT: Lazy<Mutex<Instant>> = Lazy::new(|| Mutex::new(Instant::now()));
+static STALL_WATCHDOG_THREAD: RwLock<Option<JoinHandle<()>>> = RwLock::new(None);
+static STALL_WATCHDOG_SHOULD_STOP: RelaxedAtomic = RelaxedAtomic::new(false);
+// Set once `send_video_nal` has pushed at least one NAL for the current connection, and cleared
+// whenever a new connection attempt starts. Keeps the watchdog from mistaking "no headset
+// connected yet" or "between streams" for a stalled encoder.
+static VIDEO_STREAM_ACTIVE: RelaxedAtomic = RelaxedAtomic::new(false);
+
 static FILESYSTEM_LAYOUT: Lazy<Layout> = Lazy::new(|| {
     afs::filesystem_layout_from_openvr_driver_root_dir(
         &alvr_server_io::get_driver_dir_from_registered().unwrap(),
@@ -114,26 +142,212 @@ static VIDEO_RECORDING_FILE: OptLazy<File> = alvr_common::lazy_mut_none();
 
 static DECODER_CONFIG: OptLazy<DecoderInitializationConfig> = alvr_common::lazy_mut_none();
 
-pub fn create_recording_file(settings: &Settings) {
-    let codec = settings.video.preferred_codec;
-    let ext = match codec {
+// Feed used only for the dashboard preview, derived from the primary encode (see below) rather
+// than produced by an encode of its own. Kept separate from
+// VIDEO_MIRROR_SENDER so watching the preview never competes with the headset stream.
+//
+// This snapshot has no second hardware encoder to drive a genuinely downscaled *continuous*
+// preview, so for now the preview feed is built by forwarding only IDRs from the primary encode:
+// dropping inter-coded frames instead would leave the preview decoder referencing frames it never
+// received, corrupting the picture until the next IDR. Forwarding whole IDRs keeps every frame
+// independently decodable (at the cost of refreshing only as often as the primary stream emits an
+// IDR) and still cuts bandwidth relative to the full encode, since inter frames are the bulk of it.
+// A real secondary encoder would replace this path outright and could stream every frame.
+static PREVIEW_MIRROR_SENDER: OptLazy<broadcast::Sender<Vec<u8>>> = alvr_common::lazy_mut_none();
+static PREVIEW_DECODER_CONFIG: OptLazy<DecoderInitializationConfig> = alvr_common::lazy_mut_none();
+
+// Lazily creates the preview broadcast channel on first subscriber, mirroring how the dashboard
+// subscribes to the full-res mirror. The web server calls this when a client opens the low-res
+// preview stream; nothing is encoded or forwarded to the preview path until someone does. Also
+// hands back the decoder config already in effect, if any: a subscriber joining mid-stream would
+// otherwise never see `set_preview_video_config_nals`'s one-time broadcast (sent before they
+// subscribed) and could never decode anything it receives. The caller is expected to write this
+// buffer to the new client before relaying anything off the returned receiver.
+pub fn preview_video_broadcast_subscribe() -> (broadcast::Receiver<Vec<u8>>, Option<Vec<u8>>) {
+    let receiver = PREVIEW_MIRROR_SENDER
+        .lock()
+        .get_or_insert_with(|| broadcast::channel(16).0)
+        .subscribe();
+
+    let config_buffer = PREVIEW_DECODER_CONFIG
+        .lock()
+        .as_ref()
+        .map(|config| config.config_buffer.clone());
+
+    (receiver, config_buffer)
+}
+
+// The codec actually in use for the current connection, which may differ from
+// `settings().video.preferred_codec` if the client can't decode it. Populated by
+// `reconcile_codec` once the client reports its decodable codecs during the handshake.
+static NEGOTIATED_CODEC: OptLazy<CodecType> = alvr_common::lazy_mut_none();
+
+fn negotiated_codec(settings: &Settings) -> CodecType {
+    NEGOTIATED_CODEC.lock().unwrap_or(settings.video.preferred_codec)
+}
+
+// Reconciles the configured codec against the codecs the connecting client reports it can
+// actually decode, falling back to the best mutually supported one and surfacing a
+// `ServerCoreEvent::CodecDowngraded` when the configured choice isn't usable. If the client
+// supports none of the fallback codecs either, surfaces `ServerCoreEvent::NoSupportedCodec`
+// instead of silently streaming a codec the client can't decode (connected but black screen).
+pub fn reconcile_codec(settings: &Settings, client_supported_codecs: &[CodecType]) -> CodecType {
+    let configured = settings.video.preferred_codec;
+
+    let negotiated = if client_supported_codecs.contains(&configured) {
+        Some(configured)
+    } else {
+        [CodecType::Hevc, CodecType::AV1, CodecType::H264]
+            .into_iter()
+            .find(|codec| client_supported_codecs.contains(codec))
+    };
+
+    let negotiated = match negotiated {
+        Some(negotiated) => negotiated,
+        None => {
+            error!(
+                "Client doesn't support any codec compatible with configured {configured:?}; \
+                 streaming anyway, but the client won't be able to decode it"
+            );
+
+            EVENTS_QUEUE
+                .lock()
+                .push_back(ServerCoreEvent::NoSupportedCodec { configured });
+
+            configured
+        }
+    };
+
+    *NEGOTIATED_CODEC.lock() = Some(negotiated);
+
+    if negotiated != configured {
+        warn!("Client can't decode configured codec {configured:?}, falling back to {negotiated:?}");
+
+        EVENTS_QUEUE
+            .lock()
+            .push_back(ServerCoreEvent::CodecDowngraded {
+                configured,
+                negotiated,
+            });
+    }
+
+    negotiated
+}
+
+// State for the current `recording.<timestamp>/` session: the directory, the segment currently
+// being written to, and the manifest entries accumulated so far. Reset by `create_recording_session`.
+static RECORDING_SESSION_DIR: OptLazy<PathBuf> = alvr_common::lazy_mut_none();
+static RECORDING_MANIFEST: Mutex<Vec<RecordingSegmentManifestEntry>> = Mutex::new(Vec::new());
+static RECORDING_SEGMENT_INDEX: AtomicU64 = AtomicU64::new(0);
+static RECORDING_SEGMENT_BYTES: AtomicU64 = AtomicU64::new(0);
+static RECORDING_SEGMENT_START_TIME: OptLazy<chrono::DateTime<chrono::Local>> =
+    alvr_common::lazy_mut_none();
+static RECORDING_SEGMENT_FIRST_TIMESTAMP: OptLazy<Duration> = alvr_common::lazy_mut_none();
+// Timestamp of the last frame actually written into the currently open segment. Tracked
+// independently of the rotation trigger so the manifest's `last_timestamp` reflects the segment
+// being closed, not whatever frame happened to cause the rotation.
+static RECORDING_SEGMENT_LAST_TIMESTAMP: OptLazy<Duration> = alvr_common::lazy_mut_none();
+
+#[derive(Serialize)]
+struct RecordingSegmentManifestEntry {
+    file_name: String,
+    start_time: chrono::DateTime<chrono::Local>,
+    first_timestamp: Duration,
+    last_timestamp: Duration,
+    codec: CodecType,
+    byte_size: u64,
+}
+
+#[derive(Serialize)]
+struct RecordingManifest<'a> {
+    segments: &'a [RecordingSegmentManifestEntry],
+}
+
+fn recording_segment_extension(codec: CodecType) -> &'static str {
+    match codec {
         CodecType::H264 => "h264",
         CodecType::Hevc => "h265",
         CodecType::AV1 => "av1",
+    }
+}
+
+fn write_recording_manifest(dir: &std::path::Path) {
+    let manifest = RecordingManifest {
+        segments: &RECORDING_MANIFEST.lock(),
     };
 
-    let path = FILESYSTEM_LAYOUT.log_dir.join(format!(
-        "recording.{}.{ext}",
-        chrono::Local::now().format("%F.%H-%M-%S")
-    ));
+    match serde_json::to_vec_pretty(&manifest) {
+        Ok(json) => {
+            if let Err(e) = std::fs::write(dir.join("manifest.json"), json) {
+                error!("Failed to write recording manifest: {e}");
+            }
+        }
+        Err(e) => error!("Failed to serialize recording manifest: {e}"),
+    }
+}
+
+// Closes the segment currently open (if any) and appends its manifest entry (a segment that
+// received no frames gets no entry, but still uses up its index), then starts the next
+// numbered segment file in the session directory, priming it with the stored decoder config so it
+// is independently decodable from that point on.
+//
+// `new_segment_first_timestamp` is the timestamp of the frame that will open the new segment, if
+// already known (e.g. the IDR that triggered the rotation). Pass `None` when it isn't known yet
+// (starting a session, or a watchdog-forced rotation) and the first frame actually written will
+// set it instead.
+fn finalize_and_rotate_recording_segment(
+    settings: &Settings,
+    new_segment_first_timestamp: Option<Duration>,
+) {
+    let Some(dir) = RECORDING_SESSION_DIR.lock().clone() else {
+        return;
+    };
+
+    let codec = negotiated_codec(settings);
+
+    if VIDEO_RECORDING_FILE.lock().take().is_some() {
+        let index = RECORDING_SEGMENT_INDEX.load(Ordering::SeqCst);
+        if let (Some(start_time), Some(first_timestamp)) = (
+            *RECORDING_SEGMENT_START_TIME.lock(),
+            *RECORDING_SEGMENT_FIRST_TIMESTAMP.lock(),
+        ) {
+            let last_timestamp = RECORDING_SEGMENT_LAST_TIMESTAMP
+                .lock()
+                .unwrap_or(first_timestamp);
+
+            RECORDING_MANIFEST
+                .lock()
+                .push(RecordingSegmentManifestEntry {
+                    file_name: format!("{index:03}.{}", recording_segment_extension(codec)),
+                    start_time,
+                    first_timestamp,
+                    last_timestamp,
+                    codec,
+                    byte_size: RECORDING_SEGMENT_BYTES.load(Ordering::SeqCst),
+                });
+            write_recording_manifest(&dir);
+        }
+
+        RECORDING_SEGMENT_INDEX.fetch_add(1, Ordering::SeqCst);
+    }
+
+    let index = RECORDING_SEGMENT_INDEX.load(Ordering::SeqCst);
+    let path = dir.join(format!("{index:03}.{}", recording_segment_extension(codec)));
 
     match File::create(path) {
         Ok(mut file) => {
+            let mut primed_bytes = 0;
             if let Some(config) = &*DECODER_CONFIG.lock() {
-                file.write_all(&config.config_buffer).ok();
+                if file.write_all(&config.config_buffer).is_ok() {
+                    primed_bytes = config.config_buffer.len() as u64;
+                }
             }
 
             *VIDEO_RECORDING_FILE.lock() = Some(file);
+            *RECORDING_SEGMENT_START_TIME.lock() = Some(chrono::Local::now());
+            *RECORDING_SEGMENT_FIRST_TIMESTAMP.lock() = new_segment_first_timestamp;
+            *RECORDING_SEGMENT_LAST_TIMESTAMP.lock() = None;
+            RECORDING_SEGMENT_BYTES.store(primed_bytes, Ordering::SeqCst);
 
             unsafe { RequestIDR() };
         }
@@ -143,6 +357,123 @@ pub fn create_recording_file(settings: &Settings) {
     }
 }
 
+pub fn create_recording_session(settings: &Settings) {
+    let dir = FILESYSTEM_LAYOUT.log_dir.join(format!(
+        "recording.{}",
+        chrono::Local::now().format("%F.%H-%M-%S")
+    ));
+
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        error!("Failed to create recording session directory: {e}");
+        return;
+    }
+
+    // A segment still open from a previous session is closed here, so that the first segment of
+    // this session is numbered 000.
+    VIDEO_RECORDING_FILE.lock().take();
+    *RECORDING_SESSION_DIR.lock() = Some(dir);
+    RECORDING_SEGMENT_INDEX.store(0, Ordering::SeqCst);
+    *RECORDING_SEGMENT_START_TIME.lock() = None;
+    RECORDING_MANIFEST.lock().clear();
+
+    finalize_and_rotate_recording_segment(settings, None);
+}
+
+// Short, self-contained clips sprinkled at randomized intervals across a session, independent of
+// `rolling_video_files`. Useful for reproducing intermittent glitches without hauling around one
+// giant recording. State is separate from the `RECORDING_*` statics above so this never disturbs
+// the primary recording or the live stream.
+static DIAGNOSTIC_CLIP_SEEDED: AtomicBool = AtomicBool::new(false);
+static DIAGNOSTIC_CLIP_NEXT_INSTANT: Lazy<Mutex<Instant>> = Lazy::new(|| Mutex::new(Instant::now()));
+static DIAGNOSTIC_CLIP_FILE: OptLazy<File> = alvr_common::lazy_mut_none();
+static DIAGNOSTIC_CLIP_PATH: OptLazy<PathBuf> = alvr_common::lazy_mut_none();
+static DIAGNOSTIC_CLIP_END_INSTANT: OptLazy<Instant> = alvr_common::lazy_mut_none();
+static DIAGNOSTIC_CLIP_START_TIMESTAMP: OptLazy<Duration> = alvr_common::lazy_mut_none();
+
+fn schedule_next_diagnostic_clip(config: &alvr_session::DiagnosticClipsConfig) {
+    // Guard against a misconfigured (inverted or otherwise empty) range: `gen_range` panics on
+    // those, and this runs on the video thread, so a bad setting must not crash the stream.
+    let (min_s, max_s) = if config.min_interval_s <= config.max_interval_s {
+        (config.min_interval_s, config.max_interval_s)
+    } else {
+        warn!(
+            "diagnostic_clips min_interval_s ({}) > max_interval_s ({}), swapping",
+            config.min_interval_s, config.max_interval_s
+        );
+        (config.max_interval_s, config.min_interval_s)
+    };
+
+    let delay_s = rand::thread_rng().gen_range(min_s..=max_s);
+    *DIAGNOSTIC_CLIP_NEXT_INSTANT.lock() = Instant::now() + Duration::from_secs(delay_s);
+}
+
+// Starts a new diagnostic clip on the next IDR once the randomized interval has elapsed, and
+// appends `nal_buffer` to any clip already in progress, closing it out once its fixed duration
+// is reached.
+fn capture_diagnostic_clip_nal(
+    config: &alvr_session::DiagnosticClipsConfig,
+    settings: &Settings,
+    target_timestamp: Duration,
+    nal_buffer: &[u8],
+    is_idr: bool,
+) {
+    if !DIAGNOSTIC_CLIP_SEEDED.swap(true, Ordering::SeqCst) {
+        schedule_next_diagnostic_clip(config);
+    }
+
+    if DIAGNOSTIC_CLIP_FILE.lock().is_none()
+        && is_idr
+        && Instant::now() >= *DIAGNOSTIC_CLIP_NEXT_INSTANT.lock()
+    {
+        let codec = negotiated_codec(settings);
+        let path = FILESYSTEM_LAYOUT.log_dir.join(format!(
+            "diagnostic.{}.{}",
+            chrono::Local::now().format("%F.%H-%M-%S-%3f"),
+            recording_segment_extension(codec)
+        ));
+
+        match File::create(&path) {
+            Ok(mut file) => {
+                if let Some(decoder_config) = &*DECODER_CONFIG.lock() {
+                    file.write_all(&decoder_config.config_buffer).ok();
+                }
+
+                *DIAGNOSTIC_CLIP_END_INSTANT.lock() = Some(Instant::now() + Duration::from_secs(config.duration_s));
+                *DIAGNOSTIC_CLIP_START_TIMESTAMP.lock() = Some(target_timestamp);
+                *DIAGNOSTIC_CLIP_PATH.lock() = Some(path);
+                *DIAGNOSTIC_CLIP_FILE.lock() = Some(file);
+            }
+            Err(e) => error!("Failed to start diagnostic clip: {e}"),
+        }
+    }
+
+    if let Some(file) = &mut *DIAGNOSTIC_CLIP_FILE.lock() {
+        file.write_all(nal_buffer).ok();
+    }
+
+    let clip_done = DIAGNOSTIC_CLIP_END_INSTANT
+        .lock()
+        .is_some_and(|end| Instant::now() >= end);
+    if clip_done {
+        DIAGNOSTIC_CLIP_FILE.lock().take();
+        DIAGNOSTIC_CLIP_END_INSTANT.lock().take();
+        let start_timestamp = DIAGNOSTIC_CLIP_START_TIMESTAMP.lock().take();
+
+        if let (Some(path), Some(captured_at)) = (DIAGNOSTIC_CLIP_PATH.lock().take(), start_timestamp)
+        {
+            let nominal_bitrate_bps = BITRATE_MANAGER.lock().last_nominal_bitrate_bps();
+
+            EVENTS_QUEUE.lock().push_back(ServerCoreEvent::DiagnosticClipCaptured {
+                path,
+                captured_at,
+                nominal_bitrate_bps,
+            });
+        }
+
+        schedule_next_diagnostic_clip(config);
+    }
+}
+
 pub fn notify_restart_driver() {
     let mut system = sysinfo::System::new_with_specifics(
         RefreshKind::new().with_processes(ProcessRefreshKind::everything()),
@@ -207,13 +538,63 @@ impl ServerCoreContext {
             CppInit();
         }
 
+        *LAST_VIDEO_NAL_INSTANT.lock() = Instant::now();
+        STALL_WATCHDOG_SHOULD_STOP.set(false);
+        *STALL_WATCHDOG_THREAD.write() = Some(thread::spawn(Self::stall_watchdog));
+
         Self {}
     }
 
+    // Polls `LAST_VIDEO_NAL_INSTANT` and, if no video NAL has gone through `send_video_nal` within
+    // the configured window, assumes the encoder or compositor is stuck: force-finalizes the
+    // current recording segment, requests a fresh IDR, and reports the stall so it's visible
+    // alongside the rest of the session statistics.
+    fn stall_watchdog() {
+        while !STALL_WATCHDOG_SHOULD_STOP.value() {
+            thread::sleep(Duration::from_millis(500));
+
+            // Nothing to watch while there's no active stream: a headset that's disconnected, or
+            // a session between streams, is idle, not stalled.
+            if !VIDEO_STREAM_ACTIVE.value() || !matches!(*LIFECYCLE_STATE.read(), LifecycleState::Resumed)
+            {
+                continue;
+            }
+
+            let data_manager_lock = SERVER_DATA_MANAGER.read();
+            let Switch::Enabled(config) = &data_manager_lock.settings().extra.capture.stall_detection
+            else {
+                continue;
+            };
+            let timeout = Duration::from_secs(config.timeout_s);
+            drop(data_manager_lock);
+
+            let stall_duration = LAST_VIDEO_NAL_INSTANT.lock().elapsed();
+            if stall_duration > timeout {
+                warn!("Video stream stalled for {stall_duration:?}, forcing recovery");
+
+                let settings_lock = SERVER_DATA_MANAGER.read();
+                finalize_and_rotate_recording_segment(settings_lock.settings(), None);
+                drop(settings_lock);
+
+                EVENTS_QUEUE.lock().push_back(ServerCoreEvent::RequestIDR);
+                EVENTS_QUEUE
+                    .lock()
+                    .push_back(ServerCoreEvent::StreamStalled { stall_duration });
+
+                // Give the recovery a full window before considering the stream stalled again.
+                *LAST_VIDEO_NAL_INSTANT.lock() = Instant::now();
+            }
+        }
+    }
+
     fn start_connection(&self) {
         // Note: Idle state is not used on the server side
         *LIFECYCLE_STATE.write() = LifecycleState::Resumed;
 
+        // A fresh connection attempt hasn't streamed anything yet; don't let the watchdog judge
+        // the handshake/idle time against the previous connection's last frame.
+        VIDEO_STREAM_ACTIVE.set(false);
+
         thread::spawn(move || {
             connection::handshake_loop();
         });
@@ -263,6 +644,11 @@ impl ServerCoreContext {
 
         if let Some(file) = &mut *VIDEO_RECORDING_FILE.lock() {
             file.write_all(&config_buffer).ok();
+            RECORDING_SEGMENT_BYTES.fetch_add(config_buffer.len() as u64, Ordering::SeqCst);
+        }
+
+        if let Switch::Enabled(_) = &SERVER_DATA_MANAGER.read().settings().video.preview {
+            self.set_preview_video_config_nals(config_buffer.clone(), codec);
         }
 
         *DECODER_CONFIG.lock() = Some(DecoderInitializationConfig {
@@ -271,11 +657,44 @@ impl ServerCoreContext {
         });
     }
 
+    // A real secondary encoder (once the native capture layer configures one) would call these two
+    // methods directly instead of `forward_preview_video_nal` deriving the feed from the primary
+    // encode, with no change needed on the receiving end.
+    fn set_preview_video_config_nals(&self, config_buffer: Vec<u8>, codec: CodecType) {
+        if let Some(sender) = &*PREVIEW_MIRROR_SENDER.lock() {
+            sender.send(config_buffer.clone()).ok();
+        }
+
+        *PREVIEW_DECODER_CONFIG.lock() = Some(DecoderInitializationConfig {
+            codec,
+            config_buffer,
+        });
+    }
+
+    fn send_preview_video_nal(&self, nal_buffer: Vec<u8>) {
+        if let Some(sender) = &*PREVIEW_MIRROR_SENDER.lock() {
+            sender.send(nal_buffer).ok();
+        }
+    }
+
+    // Forwards only IDRs to the preview mirror. Forwarding inter-coded frames too would reference
+    // frames the preview decoder never received (since the primary encode's P/B-frames aren't sent
+    // whole), corrupting the picture between IDRs instead of producing a usable thumbnail; an
+    // IDR-only feed is slower to refresh but every frame is independently decodable on its own.
+    fn forward_preview_video_nal(&self, nal_buffer: &[u8], is_idr: bool) {
+        if is_idr {
+            self.send_preview_video_nal(nal_buffer.to_vec());
+        }
+    }
+
     fn send_video_nal(&self, target_timestamp: Duration, nal_buffer: Vec<u8>, is_idr: bool) {
         // start in the corrupts state, the client didn't receive the initial IDR yet.
         static STREAM_CORRUPTED: AtomicBool = AtomicBool::new(true);
         static LAST_IDR_INSTANT: Lazy<Mutex<Instant>> = Lazy::new(|| Mutex::new(Instant::now()));
 
+        *LAST_VIDEO_NAL_INSTANT.lock() = Instant::now();
+        VIDEO_STREAM_ACTIVE.set(true);
+
         if let Some(sender) = &*VIDEO_CHANNEL_SENDER.lock() {
             let buffer_size = nal_buffer.len();
 
@@ -283,38 +702,61 @@ impl ServerCoreContext {
                 STREAM_CORRUPTED.store(false, Ordering::SeqCst);
             }
 
-            if let Switch::Enabled(config) = &SERVER_DATA_MANAGER
-                .read()
-                .settings()
-                .extra
-                .capture
-                .rolling_video_files
-            {
-                if Instant::now()
-                    > *LAST_IDR_INSTANT.lock() + Duration::from_secs(config.duration_s)
-                {
+            // Resolve settings once: re-locking `SERVER_DATA_MANAGER` for read while this guard is
+            // still held risks a deadlock against a writer queued in between (parking_lot's RwLock
+            // is writer-preferring, so a "recursive" read isn't guaranteed to succeed).
+            let settings_lock = SERVER_DATA_MANAGER.read();
+            let settings = settings_lock.settings();
+
+            let should_forward_frame =
+                !STREAM_CORRUPTED.load(Ordering::SeqCst) || !settings.connection.avoid_video_glitching;
+
+            if let Switch::Enabled(config) = &settings.extra.capture.rolling_video_files {
+                let elapsed =
+                    Instant::now() > *LAST_IDR_INSTANT.lock() + Duration::from_secs(config.duration_s);
+                let oversized =
+                    RECORDING_SEGMENT_BYTES.load(Ordering::SeqCst) > config.max_segment_bytes;
+
+                if elapsed || oversized {
                     EVENTS_QUEUE.lock().push_back(ServerCoreEvent::RequestIDR);
 
                     if is_idr {
-                        crate::create_recording_file(SERVER_DATA_MANAGER.read().settings());
+                        // The IDR about to open the new segment is also its first frame.
+                        finalize_and_rotate_recording_segment(settings, Some(target_timestamp));
                         *LAST_IDR_INSTANT.lock() = Instant::now();
                     }
                 }
             }
 
-            if !STREAM_CORRUPTED.load(Ordering::SeqCst)
-                || !SERVER_DATA_MANAGER
-                    .read()
-                    .settings()
-                    .connection
-                    .avoid_video_glitching
-            {
+            if let Switch::Enabled(clip_config) = &settings.extra.capture.diagnostic_clips {
+                capture_diagnostic_clip_nal(
+                    clip_config,
+                    settings,
+                    target_timestamp,
+                    &nal_buffer,
+                    is_idr,
+                );
+            }
+
+            if let Switch::Enabled(_) = &settings.video.preview {
+                if should_forward_frame {
+                    self.forward_preview_video_nal(&nal_buffer, is_idr);
+                }
+            }
+
+            if should_forward_frame {
                 if let Some(sender) = &*VIDEO_MIRROR_SENDER.lock() {
                     sender.send(nal_buffer.clone()).ok();
                 }
 
                 if let Some(file) = &mut *VIDEO_RECORDING_FILE.lock() {
                     file.write_all(&nal_buffer).ok();
+                    RECORDING_SEGMENT_BYTES.fetch_add(nal_buffer.len() as u64, Ordering::SeqCst);
+
+                    if RECORDING_SEGMENT_FIRST_TIMESTAMP.lock().is_none() {
+                        *RECORDING_SEGMENT_FIRST_TIMESTAMP.lock() = Some(target_timestamp);
+                    }
+                    *RECORDING_SEGMENT_LAST_TIMESTAMP.lock() = Some(target_timestamp);
                 }
 
                 if matches!(
@@ -335,6 +777,8 @@ impl ServerCoreContext {
                 warn!("Dropping video packet. Reason: Waiting for IDR frame");
             }
 
+            drop(settings_lock);
+
             if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
                 let encoder_latency = stats.report_frame_encoded(target_timestamp, buffer_size);
 
@@ -347,12 +791,32 @@ impl ServerCoreContext {
         }
     }
 
+    // Called by `connection` whenever the client reports having received a video packet, so the
+    // ABR controller can compute a true network goodput/buffer-occupancy estimate instead of only
+    // relying on local encode-side stats.
+    fn report_video_packet_delivery(
+        &self,
+        target_timestamp: Duration,
+        received_bytes: usize,
+        client_receive_instant: Duration,
+    ) {
+        if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+            stats.report_packet_received(target_timestamp, received_bytes, client_receive_instant);
+        }
+    }
+
     fn get_dynamic_encoder_params(&self) -> Option<DynamicEncoderParams> {
+        let network_estimate = STATISTICS_MANAGER
+            .lock()
+            .as_ref()
+            .map(|stats| stats.goodput_estimate());
+
         let pair = {
             let server_data_lock = SERVER_DATA_MANAGER.read();
-            BITRATE_MANAGER
-                .lock()
-                .get_encoder_params(&server_data_lock.settings().video.bitrate)
+            BITRATE_MANAGER.lock().get_encoder_params(
+                &server_data_lock.settings().video.bitrate,
+                network_estimate,
+            )
         };
 
         if let Some((params, stats)) = pair {
@@ -402,6 +866,12 @@ impl Drop for ServerCoreContext {
         // Invoke connection runtimes shutdown
         *LIFECYCLE_STATE.write() = LifecycleState::ShuttingDown;
 
+        VIDEO_STREAM_ACTIVE.set(false);
+        STALL_WATCHDOG_SHOULD_STOP.set(true);
+        if let Some(thread) = STALL_WATCHDOG_THREAD.write().take() {
+            thread.join().ok();
+        }
+
         {
             let mut data_manager_lock = SERVER_DATA_MANAGER.write();
 
@@ -0,0 +1,197 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// Network goodput and queueing as observed from the client's side, over a short trailing window.
+// `BitrateManager` steps the bitrate down to `bps` whenever it falls below the current bitrate,
+// and holds off any step-up while `buffer_occupancy` is high: a filling send queue shows up as
+// rising end-to-end latency well before it shows up as reduced throughput.
+#[derive(Clone, Copy)]
+pub struct GoodputEstimate {
+    pub bps: u64,
+    pub buffer_occupancy: Duration,
+}
+
+// Snapshot of how the last bitrate decision was reached, kept for the dashboard/event log so
+// chosen-vs-available bitrate (and why they differ) can be graphed over time.
+#[derive(Clone, Copy, Default)]
+pub struct NominalBitrateStats {
+    pub scaled_calculated_bitrate_bps: u64,
+    pub network_goodput_limiter_bitrate_bps: Option<u64>,
+    pub network_queue_occupancy_ms: Option<u64>,
+    pub encoder_latency_limiter_bitrate_bps: Option<u64>,
+}
+
+// Per-frame bookkeeping, keyed by the frame's presentation timestamp so that composed/present/
+// encoded reports (arriving from different threads, in different orders) can all land in the same
+// slot. Delivery reports are kept separately, in `DeliverySample`s.
+struct FrameSlot {
+    target_timestamp: Duration,
+    present_instant: Option<Instant>,
+    encoder_latency: Option<Duration>,
+}
+
+// One client acknowledgement of a delivered video packet, used to derive `goodput_estimate()`.
+// `end_to_end_latency` is the client's own receive timestamp (shared clock domain with
+// `target_timestamp`, the frame's presentation time) minus that presentation time: the full
+// render-to-delivery latency, as the client saw it, not just how long the server's send took.
+struct DeliverySample {
+    received_instant: Instant,
+    received_bytes: usize,
+    end_to_end_latency: Duration,
+}
+
+// Tracks per-frame pipeline latency and network goodput over a trailing window. Held in the
+// `STATISTICS_MANAGER` static and fed by `ServerCoreContext` from `send_video_nal`/`report_composed`/`report_present` (encode
+// side) and `report_video_packet_delivery` (client feedback forwarded through `connection`).
+pub struct StatisticsManager {
+    max_history_size: usize,
+    history: VecDeque<FrameSlot>,
+    delivery_history: VecDeque<DeliverySample>,
+    goodput_window: Duration,
+    frame_interval: Duration,
+    last_vsync_instant: Instant,
+    last_nominal_bitrate_stats: Option<NominalBitrateStats>,
+}
+
+impl StatisticsManager {
+    pub fn new(max_history_size: usize, initial_framerate: f32) -> Self {
+        Self {
+            max_history_size,
+            history: VecDeque::new(),
+            delivery_history: VecDeque::new(),
+            goodput_window: Duration::from_secs(1),
+            frame_interval: Duration::from_secs_f32(1.0 / initial_framerate.max(1.0)),
+            last_vsync_instant: Instant::now(),
+            last_nominal_bitrate_stats: None,
+        }
+    }
+
+    fn slot_mut(&mut self, target_timestamp: Duration) -> &mut FrameSlot {
+        if let Some(index) = self
+            .history
+            .iter()
+            .position(|slot| slot.target_timestamp == target_timestamp)
+        {
+            &mut self.history[index]
+        } else {
+            if self.history.len() >= self.max_history_size {
+                self.history.pop_front();
+            }
+
+            self.history.push_back(FrameSlot {
+                target_timestamp,
+                present_instant: None,
+                encoder_latency: None,
+            });
+
+            self.history.back_mut().unwrap()
+        }
+    }
+
+    // `offset` is reserved for the composed/present timing detail the graphics layer reports
+    // (time spent in the compositor vs waiting for vsync); only the instant is needed here to
+    // anchor the encoder-latency measurement.
+    pub fn report_frame_composed(&mut self, target_timestamp: Duration, _offset: Duration) {
+        self.slot_mut(target_timestamp);
+    }
+
+    pub fn report_frame_present(&mut self, target_timestamp: Duration, _offset: Duration) {
+        self.last_vsync_instant = Instant::now();
+        self.slot_mut(target_timestamp).present_instant = Some(self.last_vsync_instant);
+    }
+
+    // Returns the encoder latency for this frame (time from presentation to encoded NAL ready),
+    // falling back to zero if the frame was never registered via `report_frame_present` (can
+    // happen for the very first frames of a stream, before any vsync has been reported).
+    pub fn report_frame_encoded(&mut self, target_timestamp: Duration, _encoded_bytes: usize) -> Duration {
+        let slot = self.slot_mut(target_timestamp);
+        let latency = slot
+            .present_instant
+            .map_or(Duration::ZERO, |instant| instant.elapsed());
+        slot.encoder_latency = Some(latency);
+
+        latency
+    }
+
+    pub fn report_packet_received(
+        &mut self,
+        target_timestamp: Duration,
+        received_bytes: usize,
+        client_receive_instant: Duration,
+    ) {
+        let now = Instant::now();
+
+        self.delivery_history.push_back(DeliverySample {
+            received_instant: now,
+            received_bytes,
+            end_to_end_latency: client_receive_instant.saturating_sub(target_timestamp),
+        });
+
+        while self
+            .delivery_history
+            .front()
+            .is_some_and(|sample| now.duration_since(sample.received_instant) > self.goodput_window)
+        {
+            self.delivery_history.pop_front();
+        }
+    }
+
+    // Sliding-window goodput and queueing: `bps` is the bytes acknowledged by the client over the
+    // trailing window divided by the window actually covered (not the fixed window size, so a
+    // freshly connected client with few samples doesn't look artificially congested).
+    // `buffer_occupancy` is the window's average end-to-end latency above its *minimum* — the
+    // minimum stands in for the link's uncongested baseline latency, so anything above it is
+    // queueing delay rather than fixed network/decoder overhead.
+    pub fn goodput_estimate(&self) -> GoodputEstimate {
+        let Some(oldest) = self.delivery_history.front() else {
+            return GoodputEstimate {
+                bps: 0,
+                buffer_occupancy: Duration::ZERO,
+            };
+        };
+
+        let total_bytes: usize = self
+            .delivery_history
+            .iter()
+            .map(|sample| sample.received_bytes)
+            .sum();
+        let elapsed = oldest.received_instant.elapsed().max(Duration::from_millis(1));
+        let bps = (total_bytes as u64 * 8 * 1000) / elapsed.as_millis().max(1) as u64;
+
+        let min_latency = self
+            .delivery_history
+            .iter()
+            .map(|sample| sample.end_to_end_latency)
+            .min()
+            .unwrap_or(Duration::ZERO);
+        let sample_count = self.delivery_history.len() as u32;
+        let avg_latency = self
+            .delivery_history
+            .iter()
+            .map(|sample| sample.end_to_end_latency)
+            .sum::<Duration>()
+            / sample_count.max(1);
+
+        GoodputEstimate {
+            bps,
+            buffer_occupancy: avg_latency.saturating_sub(min_latency),
+        }
+    }
+
+    pub fn report_nominal_bitrate_stats(&mut self, stats: NominalBitrateStats) {
+        self.last_nominal_bitrate_stats = Some(stats);
+    }
+
+    // Latest decision recorded by `report_nominal_bitrate_stats`, for the dashboard to graph
+    // chosen-vs-available bitrate over time. `None` until the first bitrate decision lands.
+    pub fn last_nominal_bitrate_stats(&self) -> Option<NominalBitrateStats> {
+        self.last_nominal_bitrate_stats
+    }
+
+    pub fn duration_until_next_vsync(&self) -> Duration {
+        let elapsed = self.last_vsync_instant.elapsed();
+        self.frame_interval.saturating_sub(elapsed)
+    }
+}